@@ -0,0 +1,6 @@
+pub mod svc_memory;
+pub mod svc_codegen;
+pub mod svc_cache;
+
+#[cfg(any(test, feature = "mock_backend"))]
+pub mod svc_mock;
@@ -0,0 +1,104 @@
+use anyhow::anyhow;
+use unicorn_engine::RegisterARM64;
+use crate::backend::Backend;
+
+/// AArch64 cache-maintenance operations that guest code can issue against
+/// an address range. `rnidbg` does not model a real data/instruction cache,
+/// so all three collapse onto the same action here: drop any cached
+/// translation for the affected lines so the next fetch re-reads the bytes
+/// that were just written. Keeping them distinct documents intent at call
+/// sites and leaves room for divergent handling later.
+///
+/// TODO: no decode hook traps `dc`/`ic` and calls
+/// [`SvcMemory::handle_cache_maintenance`] yet; until one does, this is
+/// reachable only via [`flush_icache_range`]'s own callers (the SVC
+/// trampoline write in `on_register`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheMaintenanceOp {
+    /// `dc cvau` — clean data cache line to point of unification.
+    CleanToPoU,
+    /// `dc civac` — clean and invalidate data cache line to point of coherency.
+    CleanAndInvalidateToPoC,
+    /// `ic ivau` — invalidate instruction cache line to point of unification.
+    InvalidateToPoU,
+}
+
+/// Derive the (data) cache line stride in bytes from a raw `ctr_el0` value:
+/// `line_size = 1 << (((ctr_el0 >> 16) & 0xf) + 2)` words, i.e. `* 4` bytes.
+/// Pulled out of [`cache_line_stride`] as pure bit math so the derivation
+/// can be unit-tested without a `Backend`.
+fn line_size_from_ctr_el0(ctr_el0: u64) -> u64 {
+    let words = 1u64 << (((ctr_el0 >> 16) & 0xf) + 2);
+    words * 4
+}
+
+/// Round `va` down to the start of the cache line that contains it.
+fn line_start(va: u64, stride: u64) -> u64 {
+    va - (va % stride)
+}
+
+fn cache_line_stride<'a, T: Clone>(backend: &Backend<'a, T>) -> anyhow::Result<u64> {
+    let ctr_el0 = backend
+        .reg_read(RegisterARM64::CTR_EL0)
+        .map_err(|e| anyhow!("svc_cache: failed reading ctr_el0: {:?}", e))?;
+    Ok(line_size_from_ctr_el0(ctr_el0))
+}
+
+/// Flush every cache line in `va_start..va_end` after a write into an
+/// executable `SvcMemRegion`, so self-modifying or JIT-like guest code that
+/// cleans-to-PoU then invalidates sees the new bytes rather than a stale
+/// translation.
+pub fn flush_icache_range<'a, T: Clone>(backend: &Backend<'a, T>, va_start: u64, va_end: u64) -> anyhow::Result<()> {
+    if va_end <= va_start {
+        return Ok(());
+    }
+    let stride = cache_line_stride(backend)?;
+    let mut line = line_start(va_start, stride);
+    while line < va_end {
+        backend
+            .ctl_remove_cache(line, line + stride)
+            .map_err(|e| anyhow!("svc_cache: failed flushing line 0x{:x}: {:?}", line, e))?;
+        line += stride;
+    }
+    Ok(())
+}
+
+/// Handle a single `dc`/`ic` maintenance instruction trapped by the
+/// emulator against `va`. Only the cache line containing `va` is affected,
+/// matching the real instructions' per-line granularity.
+///
+/// Called from [`SvcMemory::handle_cache_maintenance`]; see that function's
+/// doc for the still-missing decode-hook wiring.
+pub fn handle_cache_maintenance_op<'a, T: Clone>(
+    backend: &Backend<'a, T>,
+    _op: CacheMaintenanceOp,
+    va: u64,
+) -> anyhow::Result<()> {
+    let stride = cache_line_stride(backend)?;
+    let line = line_start(va, stride);
+    flush_icache_range(backend, line, line + stride)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_size_decodes_common_ctr_el0_values() {
+        // IminLine/DminLine field = 0b0100 -> 1 << (4+2) = 64 words = 256 bytes,
+        // a common Cortex-A ctr_el0 cache-line encoding.
+        let ctr_el0 = 0b0100u64 << 16;
+        assert_eq!(line_size_from_ctr_el0(ctr_el0), 256);
+
+        // field = 0 -> 1 << 2 = 4 words = 16 bytes (the architectural minimum).
+        assert_eq!(line_size_from_ctr_el0(0), 16);
+    }
+
+    #[test]
+    fn line_start_rounds_down_to_stride() {
+        assert_eq!(line_start(0x1004, 0x40), 0x1000);
+        assert_eq!(line_start(0x1000, 0x40), 0x1000);
+        assert_eq!(line_start(0x103f, 0x40), 0x1000);
+        assert_eq!(line_start(0x1040, 0x40), 0x1040);
+    }
+}
@@ -0,0 +1,266 @@
+use anyhow::anyhow;
+use unicorn_engine::RegisterARM64;
+use crate::emulator::AndroidEmulator;
+use crate::memory::svc_memory::Arm64Svc;
+
+/// AAPCS64 integer argument registers, in positional order.
+const ARG_REGISTERS: [RegisterARM64; 8] = [
+    RegisterARM64::X0,
+    RegisterARM64::X1,
+    RegisterARM64::X2,
+    RegisterARM64::X3,
+    RegisterARM64::X4,
+    RegisterARM64::X5,
+    RegisterARM64::X6,
+    RegisterARM64::X7,
+];
+
+/// A C/JNI scalar type as it appears in a prototype string, reduced to the
+/// information needed to decode it out of an AAPCS64 argument slot: its
+/// width in bits and whether it should be sign-extended to 64 bits.
+///
+/// Pointers (`char*`, `JNIEnv*`, `jobject`, ...) are treated as opaque `u64`
+/// addresses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CType {
+    Void,
+    Bool,
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    I64,
+    U64,
+    Pointer,
+}
+
+impl CType {
+    fn parse(raw: &str) -> anyhow::Result<CType> {
+        let raw = raw.trim();
+        if raw.ends_with('*') || raw.contains('*') {
+            return Ok(CType::Pointer);
+        }
+        // Strip leading qualifiers that don't affect the register shape.
+        // `const` may precede `unsigned` (e.g. "const unsigned char"), so
+        // `unsigned` must be detected after `const` is stripped, not before.
+        let without_const = raw.trim_start_matches("const ").trim();
+        let unsigned = without_const.starts_with("unsigned ");
+        let stripped = without_const.trim_start_matches("unsigned ").trim();
+        Ok(match stripped {
+            "void" => CType::Void,
+            "jboolean" | "bool" => CType::Bool,
+            "jbyte" | "int8_t" | "char" => if unsigned { CType::U8 } else { CType::I8 },
+            "uint8_t" => CType::U8,
+            "jchar" | "jshort" | "int16_t" | "short" => if unsigned { CType::U16 } else { CType::I16 },
+            "uint16_t" => CType::U16,
+            "jint" | "int32_t" | "int" => if unsigned { CType::U32 } else { CType::I32 },
+            "uint32_t" => CType::U32,
+            "jlong" | "int64_t" | "long" | "long long" | "size_t" | "jsize" => {
+                if unsigned { CType::U64 } else { CType::I64 }
+            }
+            "uint64_t" => CType::U64,
+            // JNI reference types are always passed as opaque handles.
+            "jobject" | "jstring" | "jclass" | "jarray" | "jthrowable" | "jweak"
+            | "jbooleanArray" | "jbyteArray" | "jcharArray" | "jshortArray" | "jintArray"
+            | "jlongArray" | "jfloatArray" | "jdoubleArray" | "jobjectArray" => CType::Pointer,
+            other => return Err(anyhow!("svc_codegen: unsupported C type `{}`", other)),
+        })
+    }
+
+    /// Decode a 64-bit register/stack slot into the value the callback
+    /// should see, sign/zero-extending sub-64-bit integers as needed.
+    fn decode(self, raw: u64) -> i64 {
+        match self {
+            CType::Void => 0,
+            CType::Bool | CType::U8 => (raw as u8) as i64,
+            CType::I8 => (raw as u8 as i8) as i64,
+            CType::U16 => (raw as u16) as i64,
+            CType::I16 => (raw as u16 as i16) as i64,
+            CType::U32 => (raw as u32) as i64,
+            CType::I32 => (raw as u32 as i32) as i64,
+            CType::U64 | CType::Pointer => raw as i64,
+            CType::I64 => raw as i64,
+        }
+    }
+}
+
+/// A parsed C prototype: `"jint NewStringUTF(JNIEnv* env, const char* bytes)"`.
+struct CPrototype {
+    #[cfg(feature = "show_svc_name")]
+    name: String,
+    params: Vec<CType>,
+}
+
+impl CPrototype {
+    fn parse(prototype: &str) -> anyhow::Result<CPrototype> {
+        let open = prototype.find('(').ok_or_else(|| anyhow!("svc_codegen: missing `(` in `{}`", prototype))?;
+        let close = prototype.rfind(')').ok_or_else(|| anyhow!("svc_codegen: missing `)` in `{}`", prototype))?;
+
+        #[cfg(feature = "show_svc_name")]
+        let name = {
+            let head = prototype[..open].trim();
+            head.rsplit(|c: char| c.is_whitespace() || c == '*')
+                .next()
+                .filter(|s| !s.is_empty())
+                .ok_or_else(|| anyhow!("svc_codegen: missing function name in `{}`", prototype))?
+                .to_string()
+        };
+
+        let args = prototype[open + 1..close].trim();
+        let mut params = Vec::new();
+        if !args.is_empty() && args != "void" {
+            for arg in args.split(',') {
+                let arg = arg.trim();
+                // An unnamed, multi-word type (e.g. a bare "long long") parses
+                // as a whole before we try to split off a trailing parameter
+                // name — otherwise the split below would land on the space
+                // *inside* the type and silently drop its second word. The
+                // documented calling convention always names parameters, so
+                // this only guards against an unnamed prototype segment.
+                let ty = if CType::parse(arg).is_ok() {
+                    arg
+                } else {
+                    // Drop the trailing parameter name, e.g. "const char* bytes" -> "const char*".
+                    match arg.rfind(|c: char| c.is_whitespace() || c == '*') {
+                        Some(idx) if !arg[idx..].trim_start_matches('*').trim().is_empty() && arg.as_bytes()[idx] != b'*' => &arg[..idx],
+                        Some(idx) => &arg[..idx + 1],
+                        None => arg,
+                    }
+                };
+                params.push(CType::parse(ty)?);
+            }
+        }
+        Ok(CPrototype {
+            #[cfg(feature = "show_svc_name")]
+            name,
+            params,
+        })
+    }
+}
+
+/// A [`Arm64Svc`] built from a C prototype string: it decodes its arguments
+/// out of `x0..x7` (spilling to the stack past the eighth) according to
+/// AAPCS64 and hands them to `callback` as sign/zero-extended `i64`s.
+struct PrototypeSvc<T: Clone> {
+    #[cfg(feature = "show_svc_name")]
+    name: String,
+    params: Vec<CType>,
+    callback: Box<dyn Fn(&AndroidEmulator<T>, &[i64]) -> anyhow::Result<Option<i64>>>,
+}
+
+/// Byte offset from `sp` of the stack-spilled argument at `index` (`index`
+/// is 0-based over *all* params; only indices `>= ARG_REGISTERS.len()`
+/// are spilled).
+fn stack_slot_offset(index: usize) -> u64 {
+    (index - ARG_REGISTERS.len()) as u64 * 8
+}
+
+impl<T: Clone> Arm64Svc<T> for PrototypeSvc<T> {
+    #[cfg(feature = "show_svc_name")]
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn handle(&self, emu: &AndroidEmulator<T>) -> anyhow::Result<Option<i64>> {
+        let backend = emu.backend();
+        let mut args = Vec::with_capacity(self.params.len());
+        for (index, ty) in self.params.iter().enumerate() {
+            let raw = if index < ARG_REGISTERS.len() {
+                backend
+                    .reg_read(ARG_REGISTERS[index])
+                    .map_err(|e| anyhow!("svc_codegen: failed reading arg {}: {:?}", index, e))?
+            } else {
+                let sp = backend
+                    .reg_read(RegisterARM64::SP)
+                    .map_err(|e| anyhow!("svc_codegen: failed reading sp: {:?}", e))?;
+                let offset = stack_slot_offset(index);
+                let mut buf = [0u8; 8];
+                backend
+                    .mem_read(sp + offset, &mut buf)
+                    .map_err(|e| anyhow!("svc_codegen: failed reading stack arg {}: {:?}", index, e))?;
+                u64::from_le_bytes(buf)
+            };
+            args.push(ty.decode(raw));
+        }
+        (self.callback)(emu, &args)
+    }
+}
+
+/// Declare a native SVC stub from its C prototype, e.g.
+///
+/// ```ignore
+/// let svc = declare_svc(
+///     "jint NewStringUTF(JNIEnv* env, const char* bytes)",
+///     |emu, args| {
+///         let (env, bytes) = (args[0] as u64, args[1] as u64);
+///         Ok(Some(make_jstring(emu, env, bytes)?))
+///     },
+/// )?;
+/// svc_memory.register_svc(svc);
+/// ```
+///
+/// The returned handler marshals arguments per AAPCS64 (`x0..x7`, then the
+/// stack) before invoking `callback`, removing the need to hand-read
+/// registers in every `Arm64Svc::handle` implementation.
+pub fn declare_svc<T: Clone + 'static>(
+    prototype: &str,
+    callback: impl Fn(&AndroidEmulator<T>, &[i64]) -> anyhow::Result<Option<i64>> + 'static,
+) -> anyhow::Result<Box<dyn Arm64Svc<T>>> {
+    let proto = CPrototype::parse(prototype)?;
+    Ok(Box::new(PrototypeSvc {
+        #[cfg(feature = "show_svc_name")]
+        name: proto.name,
+        params: proto.params,
+        callback: Box::new(callback),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_multi_arg_jni_prototype() {
+        let proto = CPrototype::parse("jint NewStringUTF(JNIEnv* env, const char* bytes)").unwrap();
+        assert_eq!(proto.params, vec![CType::Pointer, CType::Pointer]);
+    }
+
+    #[test]
+    fn parses_void_arg_prototype() {
+        let proto = CPrototype::parse("void foo(void)").unwrap();
+        assert!(proto.params.is_empty());
+
+        let proto = CPrototype::parse("void bar()").unwrap();
+        assert!(proto.params.is_empty());
+    }
+
+    #[test]
+    fn parses_nine_params_and_spills_the_ninth_to_the_stack() {
+        let prototype = "void many(int a, int b, int c, int d, int e, int f, int g, int h, int i)";
+        let proto = CPrototype::parse(prototype).unwrap();
+        assert_eq!(proto.params.len(), 9);
+        // Indices 0..8 are register args; index 8 (the 9th param) is the
+        // first stack-spilled slot, at sp+0.
+        assert_eq!(stack_slot_offset(8), 0);
+        assert_eq!(stack_slot_offset(9), 8);
+    }
+
+    #[test]
+    fn unsigned_qualifier_is_detected_after_const() {
+        assert_eq!(CType::parse("const unsigned char").unwrap(), CType::U8);
+        assert_eq!(CType::parse("unsigned int").unwrap(), CType::U32);
+        assert_eq!(CType::parse("char").unwrap(), CType::I8);
+        assert_eq!(CType::parse("const char").unwrap(), CType::I8);
+    }
+
+    #[test]
+    fn unnamed_multi_word_type_is_not_split_on_its_internal_space() {
+        // "long long" with no parameter name: the naive trailing-word split
+        // would land between the two words and silently parse just "long".
+        let proto = CPrototype::parse("void f(long long)").unwrap();
+        assert_eq!(proto.params, vec![CType::I64]);
+    }
+}
+
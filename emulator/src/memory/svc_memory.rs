@@ -1,11 +1,16 @@
 use std::collections::HashMap;
 use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
 use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use anyhow::anyhow;
 use bytes::{BufMut, BytesMut};
-use log::info;
+use log::{debug, info, warn};
 use crate::backend::{Backend, Permission};
 use crate::emulator::{AndroidEmulator, VMPointer, SVC_BASE, SVC_MAX, SVC_SIZE};
+use crate::memory::svc_cache::flush_icache_range;
 use crate::tool::align_size;
 
 #[repr(C)]
@@ -20,17 +25,220 @@ pub struct SvcMemRegion {
     pub offset: u64
 }
 
+/// Running counters for a single SVC number: how often it fired, how much
+/// emulated handler time it cost in total, and when it was last seen.
+/// Updated from [`SvcMemory::dispatch`] on every call, so handler authors
+/// never need to instrument `handle` themselves.
+#[derive(Debug, Default)]
+pub struct SvcCallStats {
+    calls: AtomicU64,
+    total_nanos: AtomicU64,
+    last_seen_unix_millis: AtomicU64,
+}
+
+impl SvcCallStats {
+    fn record(&self, elapsed: Duration) {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        self.total_nanos.fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+        self.last_seen_unix_millis.store(now, Ordering::Relaxed);
+    }
+
+    pub fn calls(&self) -> u64 {
+        self.calls.load(Ordering::Relaxed)
+    }
+
+    pub fn total_time(&self) -> Duration {
+        Duration::from_nanos(self.total_nanos.load(Ordering::Relaxed))
+    }
+
+    pub fn last_seen_unix_millis(&self) -> u64 {
+        self.last_seen_unix_millis.load(Ordering::Relaxed)
+    }
+}
+
+/// A point-in-time view of one SVC's counters, as returned by
+/// [`SvcMemory::snapshot`].
+#[derive(Debug, Clone)]
+pub struct SvcCallSnapshot {
+    pub svc_number: u32,
+    pub name: String,
+    pub calls: u64,
+    pub total_time: Duration,
+    pub last_seen_unix_millis: u64,
+}
+
+/// Shared, `Send + Sync` handle to the per-SVC counters so the background
+/// aggregator spawned by [`SvcMemory::enable_stats`] can read them without
+/// borrowing the (non-`Send`) `SvcMemory` itself.
+#[derive(Default)]
+struct SvcStatsRegistry {
+    counters: Mutex<HashMap<u32, (String, Arc<SvcCallStats>)>>,
+}
+
+impl SvcStatsRegistry {
+    fn stats_for(&self, number: u32, name: &str) -> Arc<SvcCallStats> {
+        let mut counters = self.counters.lock().unwrap();
+        counters
+            .entry(number)
+            .or_insert_with(|| (name.to_string(), Arc::new(SvcCallStats::default())))
+            .1
+            .clone()
+    }
+
+    fn snapshot(&self) -> Vec<SvcCallSnapshot> {
+        self.counters
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(number, (name, stats))| SvcCallSnapshot {
+                svc_number: *number,
+                name: name.clone(),
+                calls: stats.calls(),
+                total_time: stats.total_time(),
+                last_seen_unix_millis: stats.last_seen_unix_millis(),
+            })
+            .collect()
+    }
+}
+
+/// Handle returned by [`SvcMemory::enable_stats`]; dropping it stops the
+/// background aggregator thread.
+///
+/// The worker waits on a channel rather than a flat `sleep(interval)`, so
+/// dropping the sender wakes it immediately instead of leaving `drop`
+/// blocked on `join` for up to a full `interval` (which, at a realistic
+/// multi-second interval, would turn shutdown into a hang).
+pub struct SvcStatsHandle {
+    stop: Option<Sender<()>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Drop for SvcStatsHandle {
+    fn drop(&mut self) {
+        // Dropping the sender closes the channel, which wakes the worker's
+        // `recv_timeout` right away regardless of how long `interval` is.
+        self.stop.take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Spawn the background thread backing [`SvcMemory::enable_stats`]: every
+/// `interval`, drain `stats`'s current histogram and emit one coalesced
+/// summary line per SVC that saw traffic since the last drain. Pulled out
+/// as a free function over just the `Arc<SvcStatsRegistry>` (rather than a
+/// `SvcMemory` method) so it can be unit-tested without a `Backend`.
+fn spawn_stats_worker(stats: Arc<SvcStatsRegistry>, interval: Duration) -> SvcStatsHandle {
+    let (stop_tx, stop_rx) = mpsc::channel::<()>();
+    let worker = std::thread::Builder::new()
+        .name("svc-stats".to_string())
+        .spawn(move || {
+            let mut previous: HashMap<u32, u64> = HashMap::new();
+            // recv_timeout blocks for at most `interval` but returns the
+            // instant the channel is closed (i.e. `stop_tx` is dropped), so
+            // SvcStatsHandle::drop never waits out a full interval.
+            while stop_rx.recv_timeout(interval) == Err(RecvTimeoutError::Timeout) {
+                for entry in stats.snapshot() {
+                    let delta = entry.calls.saturating_sub(*previous.get(&entry.svc_number).unwrap_or(&0));
+                    if delta == 0 {
+                        continue;
+                    }
+                    previous.insert(entry.svc_number, entry.calls);
+                    let rate = delta as f64 / interval.as_secs_f64().max(f64::MIN_POSITIVE);
+                    info!(
+                        "svc stats: name={} svc_number=0x{:x} calls={} (+{}, {:.1}/s) total_time={:?}",
+                        entry.name, entry.svc_number, entry.calls, delta, rate, entry.total_time
+                    );
+                }
+            }
+        })
+        .expect("failed to spawn svc-stats thread");
+    SvcStatsHandle { stop: Some(stop_tx), worker: Some(worker) }
+}
+
 pub struct SvcMemory<'a, T: Clone> {
     base: VMPointer<'a, T>,
     mem_region: Vec<SvcMemRegion>,
     arm_svc_number: u32,
-    svc_map: HashMap<u32, Box<dyn Arm64Svc<T> + 'a>>
+    svc_map: HashMap<u32, Box<dyn Arm64Svc<T> + 'a>>,
+    stats: Arc<SvcStatsRegistry>,
+    backend: Backend<'a, T>,
+    /// Spans returned by `free`, kept sorted by `begin` and coalesced
+    /// whenever two spans become adjacent, so `allocate` can reuse them
+    /// instead of only ever bumping `base` forward.
+    free_spans: Vec<(u64, u64)>,
+    /// Trampoline allocation for each registered SVC number, so
+    /// `unregister_svc` can free it without the caller tracking the address.
+    svc_alloc: HashMap<u32, u64>,
 }
 
 impl<'a, T: Clone> SvcMemory<'a, T> {
     pub(crate) fn get_svc(&self, number: u32) -> Option<&Box<dyn Arm64Svc<T> + 'a>> {
         self.svc_map.get(&number)
     }
+
+    /// Look up and invoke the SVC registered under `number`, timing the
+    /// call and folding it into that SVC's [`SvcCallStats`]. This is the
+    /// instrumented counterpart of `get_svc(number).handle(emu)`.
+    ///
+    /// TODO: no SVC-trap call site exists yet to call this instead of
+    /// `get_svc(...).handle(...)` directly, so `enable_stats`/`snapshot`
+    /// read zero until one is wired up.
+    pub fn dispatch(&self, number: u32, emu: &AndroidEmulator<T>) -> anyhow::Result<Option<i64>> {
+        let svc = self
+            .svc_map
+            .get(&number)
+            .ok_or_else(|| anyhow!("svc not registered: 0x{:x}", number))?;
+
+        #[cfg(feature = "show_svc_name")]
+        let name = svc.name().to_string();
+        #[cfg(not(feature = "show_svc_name"))]
+        let name = format!("svc#0x{:x}", number);
+
+        let stats = self.stats.stats_for(number, &name);
+        let started = Instant::now();
+        let result = svc.handle(emu);
+        stats.record(started.elapsed());
+        result
+    }
+
+    /// Start a background thread that, every `interval`, drains the current
+    /// per-SVC histogram and emits one coalesced summary line per SVC
+    /// through the `log` facade instead of logging every individual call.
+    /// Returns a handle that stops the thread when dropped.
+    pub fn enable_stats(&self, interval: Duration) -> SvcStatsHandle {
+        spawn_stats_worker(self.stats.clone(), interval)
+    }
+
+    /// Current histogram of per-SVC call counts, for callers that want to
+    /// profile which JNI/libc shims dominate a trace without waiting for
+    /// the next periodic log line.
+    pub fn snapshot(&self) -> Vec<SvcCallSnapshot> {
+        self.stats.snapshot()
+    }
+
+    /// Flush the emulated instruction cache over `va_start..va_end`. Called
+    /// after writing into an executable `SvcMemRegion` (e.g. an SVC
+    /// trampoline) so guest code that cleans-to-PoU then invalidates, or a
+    /// fresh fetch by the CPU itself, observes the bytes that were just
+    /// written rather than a stale translation.
+    pub(crate) fn flush_icache(&self, va_start: u64, va_end: u64) {
+        if let Err(e) = flush_icache_range(&self.backend, va_start, va_end) {
+            warn!("svc_memory: failed to flush icache 0x{:x}..0x{:x}: {:?}", va_start, va_end, e);
+        }
+    }
+
+    /// Forward a trapped `dc cvau` / `dc civac` / `ic ivau` against `va` to
+    /// the cache model so self-modifying guest code behaves as expected.
+    ///
+    /// TODO: no decode hook traps `dc`/`ic` and calls this yet, so it's
+    /// unreachable from guest execution until one does; `flush_icache`
+    /// (above) covers the write side in the meantime.
+    pub fn handle_cache_maintenance(&self, op: crate::memory::svc_cache::CacheMaintenanceOp, va: u64) -> anyhow::Result<()> {
+        crate::memory::svc_cache::handle_cache_maintenance_op(&self.backend, op, va)
+    }
 }
 
 impl<'a, T: Clone> SvcMemory<'a, T> {
@@ -41,35 +249,95 @@ impl<'a, T: Clone> SvcMemory<'a, T> {
             base: VMPointer::new(SVC_BASE, SVC_SIZE, backend.clone()),
             mem_region: Vec::new(),
             arm_svc_number: 0x200, // 避免占用系统调用
-            svc_map: HashMap::new()
+            svc_map: HashMap::new(),
+            stats: Arc::new(SvcStatsRegistry::default()),
+            backend: backend.clone(),
+            free_spans: Vec::new(),
+            svc_alloc: HashMap::new(),
         })
     }
 
     pub fn register_svc(&mut self, svc_box: Box<dyn Arm64Svc<T> + 'a>) -> u64 {
-        if option_env!("PRINT_SVC_REGISTER").unwrap_or("") == "1" {
-            println!("register_svc: name={}, svc_number=0x{:x}", &svc_box.name(), self.arm_svc_number + 1);
-        }
-        let pointer = unsafe {
-            let number = {
-                self.arm_svc_number += 1;
-                self.arm_svc_number
-            };
-            let pointer = svc_box.on_register(self, number);
-
-            self.svc_map.insert(number, svc_box);
-            pointer
-        };
+        self.arm_svc_number += 1;
+        let number = self.arm_svc_number;
+
+        #[cfg(feature = "show_svc_name")]
+        debug!("register_svc: name={}, svc_number=0x{:x}", &svc_box.name(), number);
+
+        let pointer = unsafe { svc_box.on_register(self, number) };
+        self.svc_map.insert(number, svc_box);
+        // Use the `number` assigned to this trampoline, not
+        // `self.arm_svc_number` — `on_register` may itself register nested
+        // SVCs (bumping the counter further), so re-reading the field here
+        // would silently associate this allocation with the wrong number.
+        self.svc_alloc.insert(number, pointer);
         pointer
     }
 
+    /// Free the SVC registered under `number`: drops it from the dispatch
+    /// map and returns its trampoline allocation to the free list. Does not
+    /// touch its accumulated [`SvcCallStats`], which remain available
+    /// through `snapshot` for historical profiling.
+    pub fn unregister_svc(&mut self, number: u32) -> anyhow::Result<()> {
+        self.svc_map
+            .remove(&number)
+            .ok_or_else(|| anyhow!("unregister_svc: svc not registered: 0x{:x}", number))?;
+        if let Some(addr) = self.svc_alloc.remove(&number) {
+            self.free(addr)?;
+        }
+        Ok(())
+    }
+
+    /// Resolve `addr` back to the (still-active) region that contains it,
+    /// for fault handlers and stack traces that need a `label`/
+    /// `library_file_path` for an address.
+    pub fn find_region(&self, addr: u64) -> Option<&SvcMemRegion> {
+        self.mem_region
+            .iter()
+            .find(|region| region.begin <= addr && addr < region.end)
+    }
+
+    /// Return a previously allocated span to the free list, coalescing it
+    /// with adjacent free spans so fragmentation doesn't accumulate across
+    /// repeated register/free cycles. The freed region is dropped from
+    /// `mem_region` immediately rather than just flagged, so a long-running
+    /// emulation that repeatedly registers and tears down shims doesn't
+    /// grow that bookkeeping `Vec` (and its `String` labels) without bound.
+    pub fn free(&mut self, pointer: u64) -> anyhow::Result<()> {
+        let index = self
+            .mem_region
+            .iter()
+            .position(|region| region.begin == pointer)
+            .ok_or_else(|| anyhow!("free: no active region at 0x{:x}", pointer))?;
+        let region = self.mem_region.remove(index);
+        release_span(&mut self.free_spans, region.begin, region.end);
+        Ok(())
+    }
+
     pub fn allocate(&mut self, size: usize, label: &str) -> VMPointer<'a, T> {
         let size = align_size(size);
-        let mut pointer = self.base.share(0);
 
-        if option_env!("PRINT_SYSCALL_LOG") == Some("1") {
-            println!("svc allocate: base=0x{:X}, size={}, label={}", pointer.addr, size, label);
+        if let Some((begin, end)) = reuse_free_span(&mut self.free_spans, size as u64) {
+            debug!("svc allocate (reused): base=0x{:X}, size={}, label={}", begin, size, label);
+
+            let mut pointer = VMPointer::new(begin, size, self.backend.clone());
+            pointer.size = size;
+            self.mem_region.push(SvcMemRegion {
+                virtual_address: begin,
+                begin,
+                end,
+                perms: Permission::READ | Permission::EXEC,
+                label: label.to_string(),
+                offset: 0,
+                library_file_path: None,
+            });
+            return pointer;
         }
 
+        let mut pointer = self.base.share(0);
+
+        debug!("svc allocate: base=0x{:X}, size={}, label={}", pointer.addr, size, label);
+
         self.base = pointer.share(size as i64);
         pointer.size = size;
 
@@ -80,13 +348,46 @@ impl<'a, T: Clone> SvcMemory<'a, T> {
             perms: Permission::READ | Permission::EXEC,
             label: label.to_string(),
             offset: 0,
-            library_file_path: None
+            library_file_path: None,
         });
 
         pointer
     }
 }
 
+/// Find a free span at least `size` bytes long, first-fit, and remove or
+/// shrink it in `free_spans`. Returns the `(begin, end)` range to allocate
+/// from; an oversized span keeps its unused remainder on the free list.
+/// Pure bookkeeping over `(begin, end)` pairs, pulled out of `allocate` so
+/// it can be unit-tested without a `Backend`.
+fn reuse_free_span(free_spans: &mut Vec<(u64, u64)>, size: u64) -> Option<(u64, u64)> {
+    let index = free_spans.iter().position(|(begin, end)| end - begin >= size)?;
+    let (begin, end) = free_spans[index];
+    if end - begin == size {
+        free_spans.remove(index);
+    } else {
+        free_spans[index] = (begin + size, end);
+    }
+    Some((begin, begin + size))
+}
+
+/// Return `[begin, end)` to `free_spans`, keeping it sorted by `begin` and
+/// coalescing with any now-adjacent span so fragmentation doesn't
+/// accumulate across repeated register/free cycles. Pulled out of `free`
+/// for the same reason as [`reuse_free_span`].
+fn release_span(free_spans: &mut Vec<(u64, u64)>, begin: u64, end: u64) {
+    free_spans.push((begin, end));
+    free_spans.sort_unstable_by_key(|span| span.0);
+    let mut coalesced: Vec<(u64, u64)> = Vec::with_capacity(free_spans.len());
+    for span in free_spans.drain(..) {
+        match coalesced.last_mut() {
+            Some(last) if last.1 == span.0 => last.1 = span.1,
+            _ => coalesced.push(span),
+        }
+    }
+    *free_spans = coalesced;
+}
+
 pub fn assemble_svc(number: u32) -> u32 {
     if number >= 0 && number < SVC_MAX - 1 {
         0xd4000001 | (number << 5)
@@ -115,10 +416,12 @@ pub trait Arm64Svc<T: Clone> {
         {
             let ptr = svc.allocate(buf.len(), format!("Arm64Svc.{}", self.name()).as_str());
             ptr.write_bytes(buf.freeze()).unwrap();
+            svc.flush_icache(ptr.addr, ptr.addr + ptr.size as u64);
             return ptr.addr;
         }
         let ptr = svc.allocate(buf.len(), "Arm64Svc");
         ptr.write_bytes(buf.freeze()).unwrap();
+        svc.flush_icache(ptr.addr, ptr.addr + ptr.size as u64);
         ptr.addr
     }
 
@@ -184,4 +487,87 @@ impl<T, S: Arm64Svc<T>> SvcContainer<T, S> {
     fn on_post_callback(&self, emulator: &AndroidEmulator<T>) -> u64 {
         self.svc.on_post_callback(emulator)
     }
-}*/
\ No newline at end of file
+}*/
+
+// TODO: these cover the allocator/stats bookkeeping through the pure
+// helpers above (`reuse_free_span`/`release_span`, `SvcStatsRegistry`,
+// `spawn_stats_worker`); a live `SvcMemory` needs a `Backend` to construct,
+// which these tests don't have access to (see `svc_mock.rs`).
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuse_free_span_takes_an_exact_size_span_first_fit() {
+        let mut spans = vec![(0x1000u64, 0x1010u64)];
+        let reused = reuse_free_span(&mut spans, 16).unwrap();
+        assert_eq!(reused, (0x1000, 0x1010));
+        assert!(spans.is_empty(), "an exact-size span should be removed outright");
+    }
+
+    #[test]
+    fn reuse_free_span_splits_the_remainder_of_a_larger_span() {
+        let mut spans = vec![(0x1000u64, 0x1020u64)];
+        let reused = reuse_free_span(&mut spans, 16).unwrap();
+        assert_eq!(reused, (0x1000, 0x1010));
+        assert_eq!(spans, vec![(0x1010, 0x1020)], "the unused remainder should stay on the free list");
+    }
+
+    #[test]
+    fn reuse_free_span_returns_none_when_nothing_fits() {
+        let mut spans = vec![(0x1000u64, 0x1008u64)];
+        assert!(reuse_free_span(&mut spans, 16).is_none());
+        assert_eq!(spans, vec![(0x1000, 0x1008)], "a span that doesn't fit must be left untouched");
+    }
+
+    #[test]
+    fn release_span_coalesces_adjacent_spans() {
+        let mut spans = vec![(0x1000u64, 0x1010u64)];
+        release_span(&mut spans, 0x1010, 0x1020);
+        assert_eq!(spans, vec![(0x1000, 0x1020)], "freeing the adjacent half should merge into one span");
+    }
+
+    #[test]
+    fn release_span_keeps_disjoint_spans_separate() {
+        let mut spans = vec![(0x1000u64, 0x1010u64)];
+        release_span(&mut spans, 0x2000, 0x2010);
+        assert_eq!(spans, vec![(0x1000, 0x1010), (0x2000, 0x2010)]);
+    }
+
+    #[test]
+    fn repeated_release_and_reuse_does_not_grow_the_free_list() {
+        let mut spans: Vec<(u64, u64)> = Vec::new();
+        release_span(&mut spans, 0x1000, 0x1010);
+        for _ in 0..100 {
+            let reused = reuse_free_span(&mut spans, 16).expect("the one span should be reused every time");
+            release_span(&mut spans, reused.0, reused.1);
+        }
+        assert_eq!(spans, vec![(0x1000, 0x1010)], "churn must not leave fragments behind");
+    }
+
+    #[test]
+    fn stats_registry_coalesces_repeated_calls_into_one_entry() {
+        let stats = SvcStatsRegistry::default();
+        assert!(stats.snapshot().is_empty());
+
+        stats.stats_for(0x201, "noop").record(Duration::from_millis(5));
+        stats.stats_for(0x201, "noop").record(Duration::from_millis(5));
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.len(), 1, "repeated stats_for(0x201, ..) must share one counter");
+        assert_eq!(snapshot[0].svc_number, 0x201);
+        assert_eq!(snapshot[0].calls, 2);
+        assert_eq!(snapshot[0].total_time, Duration::from_millis(10));
+    }
+
+    #[test]
+    fn enable_stats_stops_promptly_even_with_a_long_interval() {
+        let stats = Arc::new(SvcStatsRegistry::default());
+        let started = Instant::now();
+        drop(spawn_stats_worker(stats, Duration::from_secs(3600)));
+        assert!(
+            started.elapsed() < Duration::from_secs(1),
+            "dropping the handle must wake the worker immediately, not wait out the interval"
+        );
+    }
+}
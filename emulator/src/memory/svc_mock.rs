@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use anyhow::anyhow;
+use unicorn_engine::RegisterARM64;
+
+/// A flat in-memory register-file-plus-address-space double, for asserting
+/// on argument marshalling and memory access in isolation from a real VM.
+///
+/// TODO: does not bridge into `Backend<'a, T>`/`AndroidEmulator<'a, T>`, so
+/// it cannot drive a full `Arm64Svc::handle(&AndroidEmulator<T>)` call;
+/// tests exercise `MockBackend`'s own reg/mem surface directly instead.
+#[derive(Debug, Default)]
+pub struct MockBackend {
+    memory: Vec<u8>,
+    base: u64,
+    registers: HashMap<RegisterARM64, u64>,
+    removed_cache_ranges: Vec<(u64, u64)>,
+}
+
+impl MockBackend {
+    /// Create a mock address space of `size` bytes starting at `base`.
+    pub fn new(base: u64, size: usize) -> MockBackend {
+        MockBackend {
+            memory: vec![0u8; size],
+            base,
+            registers: HashMap::new(),
+            removed_cache_ranges: Vec::new(),
+        }
+    }
+
+    fn offset_of(&self, addr: u64) -> anyhow::Result<usize> {
+        addr.checked_sub(self.base)
+            .filter(|off| (*off as usize) < self.memory.len())
+            .map(|off| off as usize)
+            .ok_or_else(|| anyhow!("MockBackend: address 0x{:x} out of range", addr))
+    }
+
+    pub fn mem_read(&self, addr: u64, buf: &mut [u8]) -> anyhow::Result<()> {
+        let off = self.offset_of(addr)?;
+        let end = off + buf.len();
+        if end > self.memory.len() {
+            return Err(anyhow!("MockBackend: read of {} bytes at 0x{:x} out of range", buf.len(), addr));
+        }
+        buf.copy_from_slice(&self.memory[off..end]);
+        Ok(())
+    }
+
+    pub fn mem_write(&mut self, addr: u64, bytes: &[u8]) -> anyhow::Result<()> {
+        let off = self.offset_of(addr)?;
+        let end = off + bytes.len();
+        if end > self.memory.len() {
+            return Err(anyhow!("MockBackend: write of {} bytes at 0x{:x} out of range", bytes.len(), addr));
+        }
+        self.memory[off..end].copy_from_slice(bytes);
+        Ok(())
+    }
+
+    pub fn reg_read(&self, reg: RegisterARM64) -> anyhow::Result<u64> {
+        Ok(*self.registers.get(&reg).unwrap_or(&0))
+    }
+
+    pub fn reg_write(&mut self, reg: RegisterARM64, value: u64) -> anyhow::Result<()> {
+        self.registers.insert(reg, value);
+        Ok(())
+    }
+
+    /// Mirrors `Backend::ctl_remove_cache`: record that `[begin, end)` was
+    /// dropped from the (nonexistent, in this mock) translation cache, so
+    /// cache-maintenance logic can be exercised and asserted on in tests.
+    pub fn ctl_remove_cache(&mut self, begin: u64, end: u64) -> anyhow::Result<()> {
+        self.removed_cache_ranges.push((begin, end));
+        Ok(())
+    }
+
+    pub fn removed_cache_ranges(&self) -> &[(u64, u64)] {
+        &self.removed_cache_ranges
+    }
+
+    /// Convenience for tests: set `x0..x7` in one call, mirroring how a
+    /// caller would lay out AAPCS64 integer arguments before an SVC fires.
+    pub fn set_args(&mut self, args: &[u64]) -> anyhow::Result<()> {
+        const ARG_REGISTERS: [RegisterARM64; 8] = [
+            RegisterARM64::X0,
+            RegisterARM64::X1,
+            RegisterARM64::X2,
+            RegisterARM64::X3,
+            RegisterARM64::X4,
+            RegisterARM64::X5,
+            RegisterARM64::X6,
+            RegisterARM64::X7,
+        ];
+        if args.len() > ARG_REGISTERS.len() {
+            return Err(anyhow!("MockBackend::set_args: only x0..x7 are supported, got {} args", args.len()));
+        }
+        for (reg, value) in ARG_REGISTERS.iter().zip(args.iter()) {
+            self.reg_write(*reg, *value)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_back_written_memory() {
+        let mut backend = MockBackend::new(0x1000, 0x100);
+        backend.mem_write(0x1000, &[1, 2, 3, 4]).unwrap();
+        let mut buf = [0u8; 4];
+        backend.mem_read(0x1000, &mut buf).unwrap();
+        assert_eq!(buf, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn rejects_out_of_range_access() {
+        let backend = MockBackend::new(0x1000, 0x10);
+        let mut buf = [0u8; 4];
+        assert!(backend.mem_read(0x2000, &mut buf).is_err());
+    }
+
+    #[test]
+    fn set_args_populates_x0_through_xn() {
+        let mut backend = MockBackend::new(0x1000, 0x10);
+        backend.set_args(&[1, 2, 3]).unwrap();
+        assert_eq!(backend.reg_read(RegisterARM64::X0).unwrap(), 1);
+        assert_eq!(backend.reg_read(RegisterARM64::X1).unwrap(), 2);
+        assert_eq!(backend.reg_read(RegisterARM64::X2).unwrap(), 3);
+        assert_eq!(backend.reg_read(RegisterARM64::X3).unwrap(), 0);
+    }
+
+    #[test]
+    fn ctl_remove_cache_records_ranges() {
+        let mut backend = MockBackend::new(0x1000, 0x100);
+        backend.ctl_remove_cache(0x1000, 0x1040).unwrap();
+        backend.ctl_remove_cache(0x1040, 0x1080).unwrap();
+        assert_eq!(backend.removed_cache_ranges(), &[(0x1000, 0x1040), (0x1040, 0x1080)]);
+    }
+}